@@ -1,16 +1,17 @@
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use futures::future;
 use sentry::{Hub, SentryFutureExt};
-use symbolic::common::{ByteView, SelfCell};
+use symbolic::common::{ByteView, CodeId, SelfCell};
 use symbolic::debuginfo::{Object, ObjectDebugSession};
 
 use crate::services::objects::{FindObject, ObjectPurpose, ObjectsActor};
 use crate::services::symcaches::{FetchSymCache, SymCacheActor, SymCacheFile};
 use crate::sources::{FileType, SourceConfig};
 use crate::types::{
-    CompleteObjectInfo, CompleteStacktrace, ObjectFileStatus, RawStacktrace, Scope,
+    CompleteObjectInfo, CompleteStacktrace, ObjectFileStatus, ObjectId, ObjectType, RawStacktrace,
+    Scope,
 };
 use crate::utils::addr::AddrMode;
 
@@ -50,15 +51,274 @@ impl<'a> SymCacheLookupResult<'a> {
 
 pub struct SourceObject(SelfCell<ByteView<'static>, Object<'static>>);
 
+/// The number of source files whose line index is kept in [`ModuleLookup`]'s cache.
+///
+/// This bounds the memory spent on the source context extraction for a single symbolication
+/// request, which matters for pathological stacktraces with many distinct source files.
+const SOURCE_LINE_INDEX_CACHE_SIZE: usize = 16;
+
+/// Computes the mapped image size of an object from its loadable segments.
+///
+/// This is the authoritative `image_size`: the *extent* of the memory mapping, i.e.
+/// `max(addr + vmsize) - min(addr)` over the loadable segments (`PT_LOAD` / Mach-O `LC_SEGMENT`).
+/// Using segment `vmsize` rather than section spans captures the full mapping — the header prefix,
+/// segment alignment padding, and trailing zero-fill (`.bss`) that sections omit — so the backfilled
+/// size never undershoots the real extent. Taking the span (rather than the highest end address)
+/// keeps the result relative to the load base, so it stays correct for `ET_EXEC` ELFs (segments at
+/// e.g. `0x400000+`) and Mach-O (segments at `0x1_0000_0000+`), not just base-zero `ET_DYN`/PIE.
+///
+/// Objects with no loadable segments (some non-native formats) fall back to the section extent.
+fn object_image_size(object: &Object) -> Option<u64> {
+    let segments = mapped_extent(
+        object
+            .segments()
+            .map(|segment| (segment.address, segment.size)),
+    );
+    segments.or_else(|| {
+        mapped_extent(
+            object
+                .sections()
+                // Only sections actually mapped into memory (a non-zero load address) count.
+                .filter(|section| section.address != 0)
+                .map(|section| (section.address, section.size)),
+        )
+    })
+}
+
+/// Returns `max(addr + size) - min(addr)` over the given `(addr, size)` ranges, if any.
+fn mapped_extent(ranges: impl IntoIterator<Item = (u64, u64)>) -> Option<u64> {
+    let mut lo = u64::MAX;
+    let mut hi = 0;
+    for (addr, size) in ranges {
+        lo = lo.min(addr);
+        hi = hi.max(addr.saturating_add(size));
+    }
+
+    (hi > lo).then_some(hi - lo)
+}
+
+/// Parses a `.gnu_debuglink` section into its companion `(filename, crc32)`.
+///
+/// The section stores a NUL-terminated filename, zero-padded to a four-byte boundary, followed by
+/// the four-byte CRC32 of the companion debug file. The CRC is stored in the ELF's native
+/// endianness, so the caller passes `little_endian` derived from the object's header.
+fn parse_gnu_debuglink(data: &[u8], little_endian: bool) -> Option<(String, u32)> {
+    let nul = data.iter().position(|&byte| byte == 0)?;
+    let filename = std::str::from_utf8(&data[..nul]).ok()?.to_owned();
+
+    let crc_offset = (nul + 1 + 3) & !3;
+    let crc_bytes: [u8; 4] = data.get(crc_offset..crc_offset + 4)?.try_into().ok()?;
+    let crc = if little_endian {
+        u32::from_le_bytes(crc_bytes)
+    } else {
+        u32::from_be_bytes(crc_bytes)
+    };
+
+    Some((filename, crc))
+}
+
+/// Computes the CRC32 used by `.gnu_debuglink` (the reflected IEEE 802.3 polynomial).
+fn gnu_debuglink_crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// A source file together with the byte offsets of each of its lines.
+///
+/// Building this once lets [`ModuleLookup::get_context_lines`] slice out a line window directly
+/// instead of re-scanning the file from the start on every frame.
+struct LineIndex {
+    source: String,
+    /// Byte offset of the start of each line, using the same line splitting as [`str::lines`].
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    /// Builds the line index for `source` in a single pass over its bytes.
+    fn new(source: String) -> Self {
+        let mut line_starts = Vec::new();
+        if !source.is_empty() {
+            line_starts.push(0);
+            for (offset, &byte) in source.as_bytes().iter().enumerate() {
+                // A trailing newline does not produce an extra (empty) line, matching `str::lines`.
+                if byte == b'\n' && offset + 1 < source.len() {
+                    line_starts.push(offset + 1);
+                }
+            }
+        }
+
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    /// Returns the contents of the 0-based `line`, stripped of its line ending.
+    ///
+    /// The stripping matches [`str::lines`] exactly: a trailing `\r` is only removed when it is
+    /// paired with a `\n` (i.e. a `\r\n` ending). A final line that ends in a lone `\r` without a
+    /// newline keeps it, just like `"foo\r".lines()` yields `"foo\r"`.
+    fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line)?;
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .copied()
+            .unwrap_or(self.source.len());
+
+        let mut contents = &self.source[start..end];
+        if let Some(stripped) = contents.strip_suffix('\n') {
+            contents = stripped.strip_suffix('\r').unwrap_or(stripped);
+        }
+        Some(contents)
+    }
+}
+
+/// A tiny bounded least-recently-used cache of per-file [`LineIndex`]es.
+///
+/// Keyed by `(module_index, abs_path)` so the same source path in different modules stays distinct.
+struct LineIndexCache {
+    capacity: usize,
+    /// Keys ordered from least- to most-recently used.
+    order: Vec<(usize, String)>,
+    entries: HashMap<(usize, String), LineIndex>,
+}
+
+impl LineIndexCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: Vec::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Marks `key` as the most recently used.
+    fn touch(&mut self, key: &(usize, String)) {
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            let key = self.order.remove(pos);
+            self.order.push(key);
+        }
+    }
+
+    /// Returns the cached [`LineIndex`] for `key`, building and inserting it via `build` on a miss.
+    ///
+    /// When `build` yields `None` nothing is cached and `None` is returned.
+    fn get_or_insert_with(
+        &mut self,
+        key: (usize, String),
+        build: impl FnOnce() -> Option<LineIndex>,
+    ) -> Option<&LineIndex> {
+        if self.entries.contains_key(&key) {
+            self.touch(&key);
+            return self.entries.get(&key);
+        }
+
+        let index = build()?;
+
+        while self.order.len() >= self.capacity {
+            let evicted = self.order.remove(0);
+            self.entries.remove(&evicted);
+        }
+
+        self.order.push(key.clone());
+        self.entries.insert(key.clone(), index);
+        self.entries.get(&key)
+    }
+}
+
 struct ModuleEntry {
     module_index: usize,
     object_info: CompleteObjectInfo,
     symcache: Option<Arc<SymCacheFile>>,
     source_object: Option<SourceObject>,
+    /// Whether `object_info.raw.image_size` is only the address-gap estimate from [`ModuleLookup::new`].
+    ///
+    /// Such estimates are replaced by the authoritative size once the real object is fetched.
+    image_size_estimated: bool,
+    /// Facts derived from the object fetched during [`ModuleLookup::fetch_symcaches`].
+    object_hints: Option<FetchedObjectHints>,
+}
+
+/// Facts derived once from a module's fetched object.
+///
+/// The object is fetched and parsed a single time while the symcaches are resolved, and both the
+/// image-size backfill and the split-debug companion lookup read from here rather than fetching and
+/// parsing the object again.
+#[derive(Default)]
+struct FetchedObjectHints {
+    /// The authoritative mapped image size, if the object exposes loadable sections.
+    image_size: Option<u64>,
+    /// The `.gnu_debuglink` companion `(filename, crc32)` for ELF objects that carry one.
+    debuglink: Option<(String, u32)>,
+    /// The object's build-id / code-id, driving the split-debug build-id layout lookup.
+    build_id: Option<CodeId>,
+}
+
+/// The address range mapped by a single image, parallel to the address-sorted `modules`.
+#[derive(Debug, Clone, Copy)]
+struct ModuleInterval {
+    start: u64,
+    /// The exclusive end address, or `None` when the size is unknown (`image_size` of `None`/`0`).
+    end: Option<u64>,
+}
+
+impl ModuleInterval {
+    /// Whether this image maps `addr`.
+    ///
+    /// An image with an unknown end is accepted as long as it starts at or below `addr`, matching
+    /// the previous linear-scan behavior.
+    fn covers(&self, addr: u64) -> bool {
+        self.start <= addr && self.end.map_or(true, |end| addr < end)
+    }
+}
+
+/// Resolves the position in the address-sorted `intervals` that maps `addr`.
+///
+/// `intervals` is binary-searched for the rightmost image starting at or below `addr`; if that
+/// image does not actually cover `addr`, we walk left using `max_end` (the running maximum of image
+/// end addresses) to find an image that fully encloses it — the overlapping-image case, where a
+/// later-starting image is contained in an earlier, larger one.
+fn resolve_abs_index(intervals: &[ModuleInterval], max_end: &[u64], addr: u64) -> Option<usize> {
+    // Rightmost entry whose start address is `<= addr`.
+    let candidate = intervals
+        .partition_point(|interval| interval.start <= addr)
+        .checked_sub(1)?;
+
+    if intervals[candidate].covers(addr) {
+        return Some(candidate);
+    }
+
+    let mut idx = candidate;
+    while max_end[idx] >= addr {
+        if intervals[idx].covers(addr) {
+            return Some(idx);
+        }
+        idx = idx.checked_sub(1)?;
+    }
+
+    None
 }
 
 pub struct ModuleLookup {
     modules: Vec<ModuleEntry>,
+    /// The mapped address range of each entry, parallel to the address-sorted `modules`.
+    intervals: Vec<ModuleInterval>,
+    /// `max_end[i]` is the largest image end address of `modules[0..=i]`.
+    ///
+    /// `modules` is sorted by start address, but a later-starting image can still be fully
+    /// contained in an earlier, larger one. This running maximum lets the address lookup walk left
+    /// from the greatest-start candidate to find such an enclosing image.
+    max_end: Vec<u64>,
+    /// Caches the per-file line index used by [`Self::get_context_lines`].
+    line_index_cache: Mutex<LineIndexCache>,
     scope: Scope,
     sources: Arc<[SourceConfig]>,
 }
@@ -72,11 +332,18 @@ impl ModuleLookup {
         let mut modules: Vec<_> = iter
             .into_iter()
             .enumerate()
-            .map(|(module_index, object_info)| ModuleEntry {
-                module_index,
-                object_info,
-                symcache: None,
-                source_object: None,
+            .map(|(module_index, object_info)| {
+                // A missing or zero incoming size means we have to fall back to the gap estimate
+                // below, until the real object tells us the authoritative size.
+                let image_size_estimated = object_info.raw.image_size.unwrap_or(0) == 0;
+                ModuleEntry {
+                    module_index,
+                    object_info,
+                    symcache: None,
+                    source_object: None,
+                    image_size_estimated,
+                    object_hints: None,
+                }
             })
             .collect();
 
@@ -94,13 +361,39 @@ impl ModuleLookup {
             false
         });
 
+        let (intervals, max_end) = Self::compute_index(&modules);
+
         Self {
             modules,
+            intervals,
+            max_end,
+            line_index_cache: Mutex::new(LineIndexCache::new(SOURCE_LINE_INDEX_CACHE_SIZE)),
             scope,
             sources,
         }
     }
 
+    /// Computes the [`ModuleInterval`]s and the `max_end` running maximum over the sorted `modules`.
+    ///
+    /// A module with an unknown end (`image_size` of `None` or `0`) contributes `u64::MAX` to the
+    /// running maximum so the enclosing-image walk never stops short of it.
+    fn compute_index(modules: &[ModuleEntry]) -> (Vec<ModuleInterval>, Vec<u64>) {
+        let mut intervals = Vec::with_capacity(modules.len());
+        let mut max_end = Vec::with_capacity(modules.len());
+        let mut running = 0;
+        for entry in modules {
+            let start = entry.object_info.raw.image_addr.0;
+            let end = match entry.object_info.raw.image_size.unwrap_or(0) {
+                0 => None,
+                size => start.checked_add(size),
+            };
+            running = running.max(end.unwrap_or(u64::MAX));
+            intervals.push(ModuleInterval { start, end });
+            max_end.push(running);
+        }
+        (intervals, max_end)
+    }
+
     /// Returns the original `CompleteObjectInfo` list in its original sorting order.
     pub fn into_inner(mut self) -> Vec<CompleteObjectInfo> {
         self.modules.sort_by_key(|entry| entry.module_index);
@@ -115,6 +408,7 @@ impl ModuleLookup {
     pub async fn fetch_symcaches(
         &mut self,
         symcache_actor: SymCacheActor,
+        objects: ObjectsActor,
         stacktraces: &[RawStacktrace],
     ) {
         let mut referenced_objects = HashSet::new();
@@ -140,19 +434,20 @@ impl ModuleLookup {
                 }
 
                 let symcache_actor = symcache_actor.clone();
+                let objects = objects.clone();
+                let sources = self.sources.clone();
+                let scope = self.scope.clone();
+                let identifier = object_id_from_object_info(&entry.object_info.raw);
                 let request = FetchSymCache {
                     object_type: entry.object_info.raw.ty,
-                    identifier: object_id_from_object_info(&entry.object_info.raw),
-                    sources: self.sources.clone(),
-                    scope: self.scope.clone(),
+                    identifier: identifier.clone(),
+                    sources: sources.clone(),
+                    scope: scope.clone(),
                 };
 
                 Some(
-                    async move {
-                        let symcache_result = symcache_actor.fetch(request).await;
-                        (idx, symcache_result)
-                    }
-                    .bind_hub(Hub::new_from_top(Hub::current())),
+                    async move { (idx, symcache_actor.fetch(request).await) }
+                        .bind_hub(Hub::new_from_top(Hub::current())),
                 )
             });
 
@@ -179,6 +474,238 @@ impl ModuleLookup {
                 entry.object_info.debug_status = status;
             }
         }
+
+        self.fetch_object_hints_for_incomplete(&objects).await;
+
+        self.backfill_image_sizes();
+
+        self.fetch_split_debug_companions(symcache_actor, objects)
+            .await;
+    }
+
+    /// Fetches object hints only for modules that still need them after the symcache pass.
+    ///
+    /// The hints (mapped size, `.gnu_debuglink`, build-id) feed two follow-ups: the image-size
+    /// backfill for modules whose size was only gap-estimated, and the split-debug lookup for
+    /// [`ObjectFileStatus::Missing`] modules. A healthy module — client-supplied `image_size` and
+    /// `Found` debug info — needs neither, so we skip the extra `FindObject`/fetch/parse for it
+    /// rather than doing it eagerly on the hot path.
+    async fn fetch_object_hints_for_incomplete(&mut self, objects: &ObjectsActor) {
+        let futures = self
+            .modules
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                entry.image_size_estimated
+                    || entry.object_info.debug_status == ObjectFileStatus::Missing
+            })
+            .map(|(idx, entry)| {
+                let objects = objects.clone();
+                let sources = self.sources.clone();
+                let scope = self.scope.clone();
+                let identifier = object_id_from_object_info(&entry.object_info.raw);
+                async move { (idx, Self::fetch_object_hints(objects, scope, sources, identifier).await) }
+                    .bind_hub(Hub::new_from_top(Hub::current()))
+            })
+            .collect::<Vec<_>>();
+
+        for (idx, hints) in future::join_all(futures).await {
+            if let Some(entry) = self.modules.get_mut(idx) {
+                entry.object_hints = Some(hints);
+            }
+        }
+    }
+
+    /// Fetches and parses the object for `identifier`, deriving the reusable [`FetchedObjectHints`].
+    ///
+    /// Fetch/parse failures degrade to empty hints rather than aborting symbolication.
+    async fn fetch_object_hints(
+        objects: ObjectsActor,
+        scope: Scope,
+        sources: Arc<[SourceConfig]>,
+        identifier: ObjectId,
+    ) -> FetchedObjectHints {
+        let find_request = FindObject {
+            filetypes: FileType::all(),
+            purpose: ObjectPurpose::Debug,
+            scope,
+            identifier,
+            sources,
+        };
+
+        let fetch = async {
+            let meta = objects.find(find_request).await.ok()?.meta?;
+            let handle = objects.fetch(meta).await.ok()?;
+
+            let view = handle.data();
+            let object = Object::parse(&view).ok()?;
+
+            // EI_DATA (byte 5 of the ELF identification) selects the endianness: `2` is big-endian.
+            let little_endian = view.get(5).copied() != Some(2);
+            let debuglink = match object {
+                Object::Elf(ref elf) => elf
+                    .section("gnu_debuglink")
+                    .and_then(|section| parse_gnu_debuglink(section.data.as_ref(), little_endian)),
+                _ => None,
+            };
+
+            Some(FetchedObjectHints {
+                image_size: object_image_size(&object),
+                debuglink,
+                build_id: object.code_id(),
+            })
+        };
+
+        fetch.await.unwrap_or_default()
+    }
+
+    /// Replaces gap-estimated image sizes with the authoritative size from the fetched object.
+    ///
+    /// [`ModuleLookup::new`] guesses `image_size` from the address gap to the next sorted module,
+    /// which is wrong for the last image and for reports with gaps between unrelated images. When
+    /// the real object was fetched its mapped (vmsize) layout gives the correct size; we apply it to
+    /// every module whose size was only estimated and keep the gap estimate as the fallback for
+    /// modules that never got a fetched object. Afterwards the lookup index is rebuilt so the
+    /// address lookup stays consistent with the corrected sizes.
+    ///
+    /// The object-derived size replaces the estimate outright, whether it is smaller or larger, so
+    /// a frame in the gap past an image's real end no longer resolves to that image.
+    fn backfill_image_sizes(&mut self) {
+        let mut corrected = false;
+        for entry in &mut self.modules {
+            if !entry.image_size_estimated {
+                continue;
+            }
+            if let Some(size) = entry.object_hints.as_ref().and_then(|hints| hints.image_size) {
+                entry.object_info.raw.image_size = Some(size);
+                entry.image_size_estimated = false;
+                corrected = true;
+            }
+        }
+
+        if corrected {
+            let (intervals, max_end) = Self::compute_index(&self.modules);
+            self.intervals = intervals;
+            self.max_end = max_end;
+        }
+    }
+
+    /// Tries to recover debug info for stripped objects via their `.gnu_debuglink` / build-id.
+    ///
+    /// A [`ObjectFileStatus::Missing`] symcache means the primary object was located but carries no
+    /// debug info — the typical Linux case where debug info ships in a separate `-dbg` package. For
+    /// those modules we reuse the `.gnu_debuglink` (companion filename + CRC32) and build-id already
+    /// read from the primary object into [`FetchedObjectHints`] and issue a second lookup keyed on
+    /// those hints. The companion's CRC32 is verified against the value embedded in `.gnu_debuglink`
+    /// before its symcache is accepted and merged into the existing [`ModuleEntry`].
+    async fn fetch_split_debug_companions(
+        &mut self,
+        symcache_actor: SymCacheActor,
+        objects: ObjectsActor,
+    ) {
+        let futures = self
+            .modules
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.object_info.debug_status == ObjectFileStatus::Missing)
+            .filter_map(|(idx, entry)| {
+                // Reuse the debuglink read while fetching the symcache; skip modules without one.
+                let (debuglink_name, debuglink_crc) =
+                    entry.object_hints.as_ref()?.debuglink.clone()?;
+                let build_id = entry.object_hints.as_ref().and_then(|h| h.build_id.clone());
+
+                let symcache_actor = symcache_actor.clone();
+                let objects = objects.clone();
+                let scope = self.scope.clone();
+                let sources = self.sources.clone();
+                let object_type = entry.object_info.raw.ty;
+                let identifier = object_id_from_object_info(&entry.object_info.raw);
+
+                Some(
+                    async move {
+                        let symcache = Self::resolve_split_debug(
+                            symcache_actor,
+                            objects,
+                            scope,
+                            sources,
+                            object_type,
+                            identifier,
+                            debuglink_name,
+                            debuglink_crc,
+                            build_id,
+                        )
+                        .await;
+                        (idx, symcache)
+                    }
+                    .bind_hub(Hub::new_from_top(Hub::current())),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for (idx, symcache) in future::join_all(futures).await {
+            if let (Some(entry), Some(symcache)) = (self.modules.get_mut(idx), symcache) {
+                entry.object_info.arch = symcache.arch();
+                entry.object_info.features.merge(symcache.features());
+                entry.object_info.candidates.merge(symcache.candidates());
+                entry.symcache = Some(symcache);
+                entry.object_info.debug_status = ObjectFileStatus::Found;
+            }
+        }
+    }
+
+    /// Resolves the split-debug companion for a single stripped module.
+    ///
+    /// `debuglink_name`/`debuglink_crc` and `build_id` come from the primary object's hints read in
+    /// [`Self::fetch_object_hints`]. Returns the companion's [`SymCacheFile`] if one was found, its
+    /// CRC32 matched the `.gnu_debuglink` value, and it actually yielded debug info.
+    #[allow(clippy::too_many_arguments)]
+    async fn resolve_split_debug(
+        symcache_actor: SymCacheActor,
+        objects: ObjectsActor,
+        scope: Scope,
+        sources: Arc<[SourceConfig]>,
+        object_type: ObjectType,
+        identifier: ObjectId,
+        debuglink_name: String,
+        debuglink_crc: u32,
+        build_id: Option<CodeId>,
+    ) -> Option<Arc<SymCacheFile>> {
+        // Drive the follow-up lookup off the split-debug hints rather than the stripped primary's
+        // own paths: the debuglink filename and the build-id layout (`<first-two-hex>/<rest>.debug`)
+        // both point at the separate `-dbg` companion. Clearing `code_file` avoids re-locating the
+        // stripped object, whose bytes would fail the CRC check below.
+        let mut companion = identifier;
+        companion.code_file = None;
+        companion.debug_file = Some(debuglink_name);
+        if build_id.is_some() {
+            companion.code_id = build_id;
+        }
+
+        // Verify the CRC32 of the located companion before trusting it.
+        let find_companion = FindObject {
+            filetypes: FileType::all(),
+            purpose: ObjectPurpose::Debug,
+            scope: scope.clone(),
+            identifier: companion.clone(),
+            sources: sources.clone(),
+        };
+        let companion_meta = objects.find(find_companion).await.ok()?.meta?;
+        let companion_file = objects.fetch(companion_meta).await.ok()?;
+        if gnu_debuglink_crc32(companion_file.data().as_ref()) != debuglink_crc {
+            return None;
+        }
+
+        let symcache = symcache_actor
+            .fetch(FetchSymCache {
+                object_type,
+                identifier: companion,
+                sources,
+                scope,
+            })
+            .await
+            .ok()?;
+
+        matches!(symcache.parse(), Ok(Some(_))).then_some(symcache)
     }
 
     /// Fetches all the sources for the modules referenced by the `stacktraces`.
@@ -279,45 +806,20 @@ impl ModuleLookup {
         addr: u64,
         addr_mode: AddrMode,
     ) -> Option<SymCacheLookupResult<'_>> {
-        match addr_mode {
-            AddrMode::Abs => {
-                for entry in self.modules.iter() {
-                    let start_addr = entry.object_info.raw.image_addr.0;
+        let sorted_idx = self.lookup_sorted_index(addr, addr_mode)?;
+        let entry = &self.modules[sorted_idx];
 
-                    if start_addr > addr {
-                        // The debug image starts at a too high address
-                        continue;
-                    }
-
-                    let size = entry.object_info.raw.image_size.unwrap_or(0);
-                    if let Some(end_addr) = start_addr.checked_add(size) {
-                        if end_addr < addr && size != 0 {
-                            // The debug image ends at a too low address and we're also confident that
-                            // end_addr is accurate (size != 0)
-                            continue;
-                        }
-                    }
+        let relative_addr = match addr_mode {
+            AddrMode::Abs => entry.object_info.abs_to_rel_addr(addr),
+            AddrMode::Rel(_) => Some(addr),
+        };
 
-                    return Some(SymCacheLookupResult {
-                        module_index: entry.module_index,
-                        object_info: &entry.object_info,
-                        symcache: entry.symcache.as_deref(),
-                        relative_addr: entry.object_info.abs_to_rel_addr(addr),
-                    });
-                }
-                None
-            }
-            AddrMode::Rel(this_module_index) => self
-                .modules
-                .iter()
-                .find(|x| x.module_index == this_module_index)
-                .map(|entry| SymCacheLookupResult {
-                    module_index: entry.module_index,
-                    object_info: &entry.object_info,
-                    symcache: entry.symcache.as_deref(),
-                    relative_addr: Some(addr),
-                }),
-        }
+        Some(SymCacheLookupResult {
+            module_index: entry.module_index,
+            object_info: &entry.object_info,
+            symcache: entry.symcache.as_deref(),
+            relative_addr,
+        })
     }
 
     /// This looks up the source of the given line, plus `n` lines above/below.
@@ -331,57 +833,272 @@ impl ModuleLookup {
         n: usize,
     ) -> Option<(Vec<String>, String, Vec<String>)> {
         let index = self.get_module_index_by_addr(addr, addr_mode)?;
-        let session = debug_sessions.get(&index)?.as_ref()?;
-        let source = session.source_by_path(abs_path).ok()??;
+
+        let mut cache = self.line_index_cache.lock().unwrap();
+        let line_index = cache.get_or_insert_with((index, abs_path.to_owned()), || {
+            let session = debug_sessions.get(&index)?.as_ref()?;
+            let source = session.source_by_path(abs_path).ok()??;
+            Some(LineIndex::new(source.to_string()))
+        })?;
 
         let lineno = lineno as usize;
         let start_line = lineno.saturating_sub(n);
         let line_diff = lineno - start_line;
 
-        let mut lines = source.lines().skip(start_line);
-        let pre_context = (&mut lines)
-            .take(line_diff.saturating_sub(1))
-            .map(|x| x.to_string())
+        // `start_line` is the 0-based index of the first line of the pre-context window; the
+        // requested line sits `line_diff - 1` lines further on (or at `start_line` when `line_diff`
+        // is `0`, preserving the old iterator-based edge semantics).
+        let pre_count = line_diff.saturating_sub(1);
+        let pre_context = (start_line..start_line + pre_count)
+            .map_while(|line| line_index.line(line).map(str::to_string))
+            .collect();
+
+        let context_line = start_line + pre_count;
+        let context = line_index.line(context_line)?.to_string();
+
+        let post_context = (context_line + 1..context_line + 1 + n)
+            .map_while(|line| line_index.line(line).map(str::to_string))
             .collect();
-        let context = lines.next()?.to_string();
-        let post_context = lines.take(n).map(|x| x.to_string()).collect();
 
         Some((pre_context, context, post_context))
     }
 
-    // TODO:
-    // * The lookup logic is mostly duplicated with `lookup_symcache`, unify the two in a followup.
-    // * The lookup performs a linear scan, even though we have a sorted list (by addr), switch this
-    //   to a binary search in a followup.
     fn get_module_index_by_addr(&self, addr: u64, addr_mode: AddrMode) -> Option<usize> {
-        match addr_mode {
-            AddrMode::Abs => {
-                for entry in self.modules.iter() {
-                    let start_addr = entry.object_info.raw.image_addr.0;
-
-                    if start_addr > addr {
-                        // The debug image starts at a too high address
-                        continue;
-                    }
-
-                    let size = entry.object_info.raw.image_size.unwrap_or(0);
-                    if let Some(end_addr) = start_addr.checked_add(size) {
-                        if end_addr < addr && size != 0 {
-                            // The debug image ends at a too low address and we're also confident that
-                            // end_addr is accurate (size != 0)
-                            continue;
-                        }
-                    }
+        let sorted_idx = self.lookup_sorted_index(addr, addr_mode)?;
+        Some(self.modules[sorted_idx].module_index)
+    }
 
-                    return Some(entry.module_index);
-                }
-                None
-            }
+    /// Resolves the position in the address-sorted `modules` list that maps `addr`.
+    ///
+    /// For [`AddrMode::Rel`] this is simply the module carrying the requested index. For
+    /// [`AddrMode::Abs`] the lookup is delegated to [`resolve_abs_index`].
+    fn lookup_sorted_index(&self, addr: u64, addr_mode: AddrMode) -> Option<usize> {
+        match addr_mode {
+            AddrMode::Abs => resolve_abs_index(&self.intervals, &self.max_end, addr),
             AddrMode::Rel(this_module_index) => self
                 .modules
                 .iter()
-                .find(|x| x.module_index == this_module_index)
-                .map(|x| x.module_index),
+                .position(|x| x.module_index == this_module_index),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the parallel `(intervals, max_end)` index from `(start, size)` pairs, mirroring
+    /// [`ModuleLookup::compute_index`] but without the surrounding module types.
+    fn index(modules: &[(u64, u64)]) -> (Vec<ModuleInterval>, Vec<u64>) {
+        let mut intervals = Vec::new();
+        let mut max_end = Vec::new();
+        let mut running = 0;
+        for &(start, size) in modules {
+            let end = if size == 0 { None } else { start.checked_add(size) };
+            running = running.max(end.unwrap_or(u64::MAX));
+            intervals.push(ModuleInterval { start, end });
+            max_end.push(running);
+        }
+        (intervals, max_end)
+    }
+
+    #[test]
+    fn resolve_abs_index_basic() {
+        let (intervals, max_end) = index(&[(0x1000, 0x100), (0x2000, 0x100)]);
+
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0x1000), Some(0));
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0x10ff), Some(0));
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0x2050), Some(1));
+        // Below the first image.
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0x500), None);
+        // In the gap between two images with known ends.
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0x1500), None);
+    }
+
+    #[test]
+    fn resolve_abs_index_enclosing() {
+        // A small image fully contained inside an earlier, larger one.
+        let (intervals, max_end) = index(&[(0x1000, 0x1000), (0x1200, 0x100)]);
+
+        // Directly inside the inner image.
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0x1250), Some(1));
+        // Inside the outer image but past the inner one: walk left to the enclosing image.
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0x1800), Some(0));
+    }
+
+    #[test]
+    fn resolve_abs_index_unknown_end() {
+        // `size == 0` means unknown end: accepted as long as the start is `<= addr`.
+        let (intervals, max_end) = index(&[(0x1000, 0)]);
+
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0x1000), Some(0));
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0xffff_ffff), Some(0));
+        assert_eq!(resolve_abs_index(&intervals, &max_end, 0x500), None);
+    }
+}
+
+#[cfg(test)]
+mod line_index_tests {
+    use super::*;
+
+    /// Asserts that [`LineIndex`] reproduces `str::lines` line-for-line for `source`.
+    fn assert_matches_std(source: &str) {
+        let index = LineIndex::new(source.to_owned());
+        let expected: Vec<&str> = source.lines().collect();
+        let actual: Vec<&str> = (0..expected.len())
+            .map(|line| index.line(line).unwrap())
+            .collect();
+        assert_eq!(actual, expected, "line index diverged for {source:?}");
+        // One past the last line is always out of range.
+        assert_eq!(index.line(expected.len()), None);
+    }
+
+    #[test]
+    fn line_splitting_matches_std() {
+        for source in [
+            "",
+            "a",
+            "a\n",
+            "a\nb",
+            "a\nb\n",
+            "a\n\nb",
+            "crlf\r\nend\r\n",
+            "trailing\r",
+            "mid\rdle",
+        ] {
+            assert_matches_std(source);
+        }
+    }
+
+    #[test]
+    fn get_context_lines_edges() {
+        // `get_context_lines` is driven by the module lookup, but the windowing math around the
+        // cached `LineIndex` is what matters for the edge cases. Replicate it here directly.
+        let index = LineIndex::new("l1\nl2\nl3\nl4\nl5".to_owned());
+
+        let window = |lineno: usize, n: usize| {
+            let start_line = lineno.saturating_sub(n);
+            let line_diff = lineno - start_line;
+            let pre_count = line_diff.saturating_sub(1);
+            let pre: Vec<String> = (start_line..start_line + pre_count)
+                .map_while(|l| index.line(l).map(str::to_string))
+                .collect();
+            let context_line = start_line + pre_count;
+            let context = index.line(context_line).map(str::to_string);
+            let post: Vec<String> = (context_line + 1..context_line + 1 + n)
+                .map_while(|l| index.line(l).map(str::to_string))
+                .collect();
+            (pre, context, post)
+        };
+
+        // `lineno <= n`: the pre-context is clamped at the start of the file.
+        let (pre, context, post) = window(1, 2);
+        assert!(pre.is_empty());
+        assert_eq!(context.as_deref(), Some("l1"));
+        assert_eq!(post, ["l2", "l3"]);
+
+        // EOF truncation: the post-context stops at the last line.
+        // The pre-context holds `n - 1` lines, as with the previous `skip`/`take` iterator.
+        let (pre, context, post) = window(5, 2);
+        assert_eq!(pre, ["l4"]);
+        assert_eq!(context.as_deref(), Some("l5"));
+        assert!(post.is_empty());
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let mut cache = LineIndexCache::new(2);
+        let build = |text: &str| {
+            let text = text.to_owned();
+            move || Some(LineIndex::new(text))
+        };
+
+        assert!(cache.get_or_insert_with((0, "a".to_owned()), build("a")).is_some());
+        assert!(cache.get_or_insert_with((0, "b".to_owned()), build("b")).is_some());
+        // Touch "a" so "b" becomes the least-recently used entry.
+        assert!(cache.get_or_insert_with((0, "a".to_owned()), build("unused")).is_some());
+        // Inserting a third entry evicts "b".
+        assert!(cache.get_or_insert_with((0, "c".to_owned()), build("c")).is_some());
+
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.entries.contains_key(&(0, "a".to_owned())));
+        assert!(cache.entries.contains_key(&(0, "c".to_owned())));
+        assert!(!cache.entries.contains_key(&(0, "b".to_owned())));
+
+        // A failed build caches nothing.
+        assert!(cache
+            .get_or_insert_with((0, "d".to_owned()), || None)
+            .is_none());
+        assert!(!cache.entries.contains_key(&(0, "d".to_owned())));
+    }
+}
+
+#[cfg(test)]
+mod debuglink_tests {
+    use super::*;
+
+    /// Builds a `.gnu_debuglink` section body: NUL-terminated name, zero-padded to four bytes,
+    /// followed by the CRC in the given endianness.
+    fn debuglink_section(name: &str, crc: u32, little_endian: bool) -> Vec<u8> {
+        let mut data = name.as_bytes().to_vec();
+        data.push(0);
+        while data.len() % 4 != 0 {
+            data.push(0);
         }
+        if little_endian {
+            data.extend_from_slice(&crc.to_le_bytes());
+        } else {
+            data.extend_from_slice(&crc.to_be_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn parses_debuglink_both_endiannesses() {
+        let le = debuglink_section("libfoo.so.debug", 0x1234_5678, true);
+        assert_eq!(
+            parse_gnu_debuglink(&le, true),
+            Some(("libfoo.so.debug".to_owned(), 0x1234_5678))
+        );
+
+        let be = debuglink_section("libfoo.so.debug", 0x1234_5678, false);
+        assert_eq!(
+            parse_gnu_debuglink(&be, false),
+            Some(("libfoo.so.debug".to_owned(), 0x1234_5678))
+        );
+
+        // Truncated section (missing CRC) is rejected.
+        assert_eq!(parse_gnu_debuglink(b"libfoo\0", true), None);
+    }
+
+    #[test]
+    fn crc32_matches_known_vector() {
+        // The canonical CRC-32 check value for the ASCII string "123456789".
+        assert_eq!(gnu_debuglink_crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(gnu_debuglink_crc32(b""), 0);
+    }
+}
+
+#[cfg(test)]
+mod object_size_tests {
+    use super::*;
+
+    #[test]
+    fn extent_is_relative_to_load_base() {
+        // ET_EXEC-style segments linked at a non-zero base: the image size is the span, not the
+        // absolute end address.
+        let segments = [(0x40_0000, 0x1000), (0x40_2000, 0x800)];
+        assert_eq!(mapped_extent(segments), Some(0x2800));
+
+        // Mach-O-style high base.
+        let macho = [(0x1_0000_0000, 0x4000)];
+        assert_eq!(mapped_extent(macho), Some(0x4000));
+
+        // Base-zero PIE: span equals the highest end.
+        let pie = [(0x0, 0x1000), (0x2000, 0x1000)];
+        assert_eq!(mapped_extent(pie), Some(0x3000));
+
+        // No mapped ranges.
+        assert_eq!(mapped_extent([]), None);
     }
 }